@@ -2,13 +2,14 @@ use std::collections::HashMap;
 
 use crate::{
     command::Command,
-    state::{AddRemovePlayerOp, AddRemovePlayerResult, Queue},
+    scheduler::{Deadline, Scheduler},
+    state::{AddRemovePlayerOp, AddRemovePlayerResult, Cadence, Queue},
     state_container::StateContainer,
     types::{ChatId, QueueId},
     util::{fmt_naive_time, mk_players_str, mk_queue_status_msg, mk_username, send_msg},
 };
-use chrono::Local;
-use teloxide::{prelude::*, Bot};
+use chrono::{Datelike, Local, NaiveTime, Utc, Weekday};
+use teloxide::{prelude::*, utils::command::BotCommands, Bot};
 
 static INSTANT_QUEUE_TIMEOUT_MINUTES: i64 = 30;
 
@@ -20,11 +21,11 @@ async fn handle_queue_timeout(
     chat_id: &ChatId,
     queue_id: &QueueId,
 ) -> Option<()> {
-    let state = sc.read().await;
+    let state = sc.read_chat(chat_id).await.ok()?;
 
-    // Remove chat queue and write new state.
-    let (state, removed_queue) = state.rm_chat_queue(chat_id, queue_id);
-    sc.write(state).await;
+    // Remove chat queue and persist just this chat.
+    let (chat, removed_queue) = state.rm_chat_queue(chat_id, queue_id);
+    sc.write_chat(*chat_id, chat).await.ok()?;
 
     let removed_queue = removed_queue?;
 
@@ -42,25 +43,206 @@ async fn handle_queue_timeout(
     Some(())
 }
 
-/// Task that polls and takes action for any queues that have timed out.
-pub async fn poll_for_timeouts(sc: StateContainer, bot: Bot) {
+/// Task that fires queue expirations as they come due. Rather than polling
+/// every second, it sleeps exactly until the earliest scheduled deadline,
+/// waking early via `Scheduler::notified` whenever `handle_cmd` schedules an
+/// earlier one. Each popped deadline is re-validated against the current
+/// state before acting on it, since the queue may have been removed or its
+/// timeout changed since it was scheduled.
+pub async fn run_scheduler(sc: StateContainer, bot: Bot, scheduler: Scheduler) {
     loop {
-        let state = sc.read().await;
-        let t = fmt_naive_time(&Local::now().time());
-
-        // Traverse all chat queues and look for timed out queues.
-        for (chat_id, chat) in &state.chats {
-            for (queue_id, queue) in &chat.queues {
-                // Note that we compare only HH:MM timestamps here and poll
-                // every second, so we shouldn't miss any timeouts.
-                if t == fmt_naive_time(&queue.timeout) {
-                    handle_queue_timeout(&sc, &bot, chat_id, queue_id).await;
+        match scheduler.peek_next().await {
+            Some(at) => {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(at) => {
+                        for deadline in scheduler.pop_due().await {
+                            fire_if_still_valid(&sc, &bot, &deadline).await;
+                        }
+                    }
+                    _ = scheduler.notified() => {
+                        // A new (possibly earlier) deadline was scheduled;
+                        // loop around and re-peek.
+                    }
                 }
             }
+            None => scheduler.notified().await,
         }
+    }
+}
+
+/// Acts on a popped deadline only if the queue still exists with the exact
+/// timeout it was scheduled for; otherwise it's stale (removed, or
+/// rescheduled to a different time) and is silently dropped.
+async fn fire_if_still_valid(sc: &StateContainer, bot: &Bot, deadline: &Deadline) {
+    let Ok(state) = sc.read_chat(&deadline.chat_id).await else {
+        return;
+    };
+
+    let still_valid = state
+        .chats
+        .get(&deadline.chat_id)
+        .and_then(|chat| chat.queues.get(&deadline.queue_id))
+        .is_some_and(|queue| queue.timeout == deadline.timeout);
+
+    if still_valid {
+        handle_queue_timeout(sc, bot, &deadline.chat_id, &deadline.queue_id).await;
+    }
+}
+
+/// Number of days from `now_weekday`/`now_time` until the next time
+/// `cadence`/`time` is due: 0 if it's still ahead of us later today, up to
+/// 7 (weekly) or 1 (daily) if it's already passed today.
+fn days_ahead(cadence: Cadence, now_weekday: Weekday, now_time: NaiveTime, time: NaiveTime) -> i64 {
+    match cadence {
+        Cadence::Daily => i64::from(time <= now_time),
+        Cadence::Weekly(weekday) => {
+            let current = now_weekday.num_days_from_monday() as i64;
+            let target = weekday.num_days_from_monday() as i64;
+            let mut diff = (target - current).rem_euclid(7);
+            if diff == 0 && time <= now_time {
+                diff = 7;
+            }
+            diff
+        }
+    }
+}
+
+/// Computes the next `Instant` a recurring schedule fires at: today or
+/// tomorrow for `Cadence::Daily`, or the next occurrence of the given
+/// weekday for `Cadence::Weekly`.
+pub fn next_occurrence(cadence: Cadence, time: NaiveTime) -> tokio::time::Instant {
+    let now = Local::now();
+    let now_time = now.time();
+
+    let days_ahead = days_ahead(cadence, now.weekday(), now_time, time);
+
+    let until = chrono::Duration::days(days_ahead) + (time - now_time);
+    tokio::time::Instant::now() + until.to_std().unwrap_or_default()
+}
 
-        // Poll again after 1 second.
-        tokio::time::sleep(std::time::Duration::from_secs(1)).await
+/// Task that materializes recurring queues as their schedules come due,
+/// mirroring `run_scheduler` but driven by `Chat::schedules` instead of
+/// queue timeouts.
+pub async fn run_recurring_schedules(
+    sc: StateContainer,
+    bot: Bot,
+    queue_scheduler: Scheduler,
+    schedule_scheduler: Scheduler,
+) {
+    loop {
+        match schedule_scheduler.peek_next().await {
+            Some(at) => {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(at) => {
+                        for deadline in schedule_scheduler.pop_due().await {
+                            fire_schedule_if_still_valid(&sc, &bot, &queue_scheduler, &schedule_scheduler, &deadline).await;
+                        }
+                    }
+                    _ = schedule_scheduler.notified() => {
+                        // A new (possibly earlier) schedule was armed; loop
+                        // around and re-peek.
+                    }
+                }
+            }
+            None => schedule_scheduler.notified().await,
+        }
+    }
+}
+
+/// Acts on a popped schedule deadline only if the schedule still exists
+/// with the exact time it was armed for. `deadline.queue_id` carries the
+/// schedule's id and `deadline.timeout` its time-of-day, reusing the
+/// queue-timeout `Deadline` type rather than introducing a parallel one.
+/// Materializes a fresh queue, arms the queue-timeout scheduler for it,
+/// announces it, and re-arms itself for the schedule's next occurrence.
+async fn fire_schedule_if_still_valid(
+    sc: &StateContainer,
+    bot: &Bot,
+    queue_scheduler: &Scheduler,
+    schedule_scheduler: &Scheduler,
+    deadline: &Deadline,
+) {
+    let Ok(state) = sc.read_chat(&deadline.chat_id).await else {
+        return;
+    };
+    let schedule_id = &deadline.queue_id.0;
+
+    let entry = state
+        .chats
+        .get(&deadline.chat_id)
+        .and_then(|chat| chat.schedules.get(schedule_id))
+        .filter(|entry| entry.time == deadline.timeout)
+        .cloned();
+
+    let Some(entry) = entry else {
+        return;
+    };
+
+    let (chat, queue_id, queue, already_existed) =
+        state.materialize_scheduled_queue(&deadline.chat_id, entry.time);
+    if sc.write_chat(deadline.chat_id, chat).await.is_err() {
+        return;
+    }
+
+    queue_scheduler
+        .schedule(deadline.chat_id, queue_id.clone(), queue.timeout)
+        .await;
+
+    let text = if already_existed {
+        let players_str = mk_players_str(&queue, false, false);
+        format!(
+            "{} queue ({}) already has players signed up:\n{}",
+            queue_id, entry.cadence, players_str
+        )
+    } else {
+        format!("{} queue is open! ({})", queue_id, entry.cadence)
+    };
+    send_msg(bot, &deadline.chat_id, &text, false).await;
+
+    let next_at = next_occurrence(entry.cadence, entry.time);
+    schedule_scheduler
+        .schedule_at(
+            next_at,
+            deadline.chat_id,
+            deadline.queue_id.clone(),
+            entry.time,
+        )
+        .await;
+}
+
+/// Builds the "Match ready" message for a freshly-filled instant queue,
+/// including the balanced rosters computed in `add_remove_player`.
+fn mk_match_ready_msg(queue_id: &QueueId, queue: &Queue) -> String {
+    let players_str = mk_players_str(queue, true, false);
+
+    match &queue.teams {
+        Some((team_a, team_b)) => format!(
+            "Match ready in {} queue! {}\n\nTeam 1: {}\nTeam 2: {}",
+            queue_id,
+            players_str,
+            team_a.join(", "),
+            team_b.join(", ")
+        ),
+        None => format!("Match ready in {} queue! {}", queue_id, players_str),
+    }
+}
+
+/// Maps the queue argument of `/result` back to a `QueueId`: `add` for the
+/// instant queue, otherwise the `HH:MM` string as-is.
+fn parse_queue_id(raw: &str) -> QueueId {
+    if raw == "add" {
+        QueueId::new(String::new())
+    } else {
+        QueueId::new(raw.to_owned())
+    }
+}
+
+/// Whether `user` is an administrator of `chat_id`, per Telegram. Used to
+/// gate the moderation commands.
+async fn is_admin(bot: &Bot, chat_id: &ChatId, user: &teloxide::types::User) -> bool {
+    match bot.get_chat_administrators(chat_id.0).await {
+        Ok(admins) => admins.iter().any(|admin| admin.user.id == user.id),
+        Err(_) => false,
     }
 }
 
@@ -80,13 +262,22 @@ fn make_queue_strings(queues: HashMap<QueueId, Queue>) -> Vec<String> {
 }
 
 /// Handler for parsed incoming Telegram commands.
-pub async fn handle_cmd(sc: StateContainer, bot: Bot, msg: Message, cmd: Command) -> Option<()> {
-    let state = sc.read().await;
+pub async fn handle_cmd(
+    sc: StateContainer,
+    bot: Bot,
+    msg: Message,
+    cmd: Command,
+    queue_scheduler: Scheduler,
+    schedule_scheduler: Scheduler,
+) -> Option<()> {
     let chat_id = ChatId::new(msg.chat.id);
+    let state = sc.read_chat(&chat_id).await.ok()?;
     let user = msg.from()?;
 
     match cmd {
-        Command::Help => send_msg(&bot, &chat_id, Command::descriptions(), true).await,
+        Command::Help => {
+            send_msg(&bot, &chat_id, &Command::descriptions().to_string(), true).await
+        }
 
         Command::AddRemove { time, for_user } => {
             let username = for_user.unwrap_or_else(|| mk_username(user));
@@ -108,17 +299,25 @@ pub async fn handle_cmd(sc: StateContainer, bot: Bot, msg: Message, cmd: Command
                 }
             };
 
-            // Add player and update state.
-            let (state, result, op) =
+            // Add player and persist just this chat.
+            let (chat, result, op) =
                 state.add_remove_player(&chat_id, &queue_id, add_cmd, timeout, username);
-            sc.write(state.clone()).await;
+            sc.write_chat(chat_id, chat).await.ok()?;
+
+            // (Re-)arm the scheduler for this queue's expiration.
+            queue_scheduler
+                .schedule(chat_id, queue_id.clone(), timeout)
+                .await;
 
             // Construct message based on whether the queue is now full or not.
             let text = match result {
                 AddRemovePlayerResult::QueueFull(queue) if queue_id.is_instant_queue() => {
-                    let players_str = mk_players_str(&queue, true, false);
-                    format!("Match ready in {} queue! {}", queue_id, players_str)
+                    mk_match_ready_msg(&queue_id, &queue)
                 }
+                AddRemovePlayerResult::PlayerBlocked(until) => format!(
+                    "You're blocked from joining queues until {}.",
+                    until.format("%Y-%m-%d %H:%M UTC")
+                ),
                 AddRemovePlayerResult::PlayerQueued(queue)
                 | AddRemovePlayerResult::QueueFull(queue)
                 | AddRemovePlayerResult::QueueEmpty(queue) => {
@@ -133,9 +332,9 @@ pub async fn handle_cmd(sc: StateContainer, bot: Bot, msg: Message, cmd: Command
         Command::RemoveAll => {
             let username = mk_username(user);
 
-            // Remove player and update state.
-            let (state, affected_queues) = state.rm_player(&chat_id, &username);
-            sc.write(state.clone()).await;
+            // Remove player and persist just this chat.
+            let (chat, affected_queues) = state.rm_player(&chat_id, &username);
+            sc.write_chat(chat_id, chat).await.ok()?;
 
             // Send queue status message for all affected queues.
             for (queue_id, queue) in affected_queues {
@@ -170,7 +369,243 @@ pub async fn handle_cmd(sc: StateContainer, bot: Bot, msg: Message, cmd: Command
 
             send_msg(&bot, &chat_id, &text, false).await
         }
+
+        Command::Result {
+            queue,
+            winning_team,
+        } => {
+            let queue_id = parse_queue_id(&queue);
+            let stored_teams = state
+                .chats
+                .get(&chat_id)
+                .and_then(|chat| chat.queues.get(&queue_id))
+                .and_then(|queue| queue.teams.clone());
+
+            let text = match stored_teams {
+                Some((team_a, team_b)) => match winning_team {
+                    1 | 2 => {
+                        let (winners, losers) = if winning_team == 1 {
+                            (&team_a, &team_b)
+                        } else {
+                            (&team_b, &team_a)
+                        };
+
+                        // Apply the Elo update and persist each changed rating.
+                        let new_ratings = state.apply_match_result(winners, losers);
+                        for username in winners.iter().chain(losers) {
+                            if let Some(rating) = new_ratings.get(username) {
+                                sc.write_rating(username.clone(), *rating).await.ok()?;
+                            }
+                        }
+
+                        // Clear the recorded teams so /result can't be
+                        // replayed to reapply the same Elo update.
+                        let chat = state.clear_queue_teams(&chat_id, &queue_id);
+                        sc.write_chat(chat_id, chat).await.ok()?;
+
+                        format!(
+                            "Recorded result for {} queue. Team {} wins!\nWinners: {}\nLosers: {}",
+                            queue_id,
+                            winning_team,
+                            winners.join(", "),
+                            losers.join(", ")
+                        )
+                    }
+                    _ => String::from("winning_team must be 1 or 2."),
+                },
+                None => format!("{} queue has no recorded teams to score.", queue_id),
+            };
+
+            send_msg(&bot, &chat_id, &text, false).await
+        }
+
+        Command::Kick { user: target } => {
+            if !is_admin(&bot, &chat_id, user).await {
+                send_msg(&bot, &chat_id, "Only chat admins can do that.", false).await;
+                return Some(());
+            }
+
+            // Remove the kicked player and persist just this chat.
+            let (chat, affected_queues) = state.rm_player(&chat_id, &target);
+            sc.write_chat(chat_id, chat).await.ok()?;
+
+            if affected_queues.is_empty() {
+                let text = format!("{} wasn't in any queue.", target);
+                send_msg(&bot, &chat_id, &text, false).await;
+            }
+
+            for (queue_id, queue) in affected_queues {
+                let text = mk_queue_status_msg(
+                    &queue,
+                    &queue_id,
+                    &AddRemovePlayerOp::PlayerRemoved(target.clone()),
+                );
+                send_msg(&bot, &chat_id, &text, false).await
+            }
+        }
+
+        Command::Block {
+            user: target,
+            duration_minutes,
+        } => {
+            if !is_admin(&bot, &chat_id, user).await {
+                send_msg(&bot, &chat_id, "Only chat admins can do that.", false).await;
+                return Some(());
+            }
+
+            let until = Utc::now() + chrono::Duration::minutes(duration_minutes);
+            let chat = state.block_player(&chat_id, target.clone(), until);
+            sc.write_chat(chat_id, chat).await.ok()?;
+
+            let text = format!(
+                "{} is blocked from joining queues until {}.",
+                target,
+                until.format("%Y-%m-%d %H:%M UTC")
+            );
+            send_msg(&bot, &chat_id, &text, false).await
+        }
+
+        Command::Unblock { user: target } => {
+            if !is_admin(&bot, &chat_id, user).await {
+                send_msg(&bot, &chat_id, "Only chat admins can do that.", false).await;
+                return Some(());
+            }
+
+            let chat = state.unblock_player(&chat_id, &target);
+            sc.write_chat(chat_id, chat).await.ok()?;
+
+            let text = format!("{} is no longer blocked.", target);
+            send_msg(&bot, &chat_id, &text, false).await
+        }
+
+        Command::Schedule { time, cadence } => {
+            let time = match NaiveTime::parse_from_str(&time, "%H%M") {
+                Ok(time) => time,
+                Err(_) => {
+                    let text = "time must look like HHMM, e.g. 2000.";
+                    send_msg(&bot, &chat_id, text, false).await;
+                    return Some(());
+                }
+            };
+            let cadence = match Cadence::parse(&cadence) {
+                Some(cadence) => cadence,
+                None => {
+                    let text = "cadence must be `daily` or a weekday (mon..sun).";
+                    send_msg(&bot, &chat_id, text, false).await;
+                    return Some(());
+                }
+            };
+
+            // Register the schedule and persist just this chat.
+            let (chat, id) = state.add_schedule(&chat_id, time, cadence);
+            sc.write_chat(chat_id, chat).await.ok()?;
+
+            // Arm the schedule scheduler for its next occurrence.
+            let next_at = next_occurrence(cadence, time);
+            schedule_scheduler
+                .schedule_at(next_at, chat_id, QueueId::new(id.clone()), time)
+                .await;
+
+            let text = format!(
+                "Scheduled {} queue {} (id {}).",
+                time.format("%H:%M"),
+                cadence,
+                id
+            );
+            send_msg(&bot, &chat_id, &text, false).await
+        }
+
+        Command::Schedules => {
+            let schedules = state
+                .chats
+                .get(&chat_id)
+                .map(|chat| chat.schedules.clone())
+                .unwrap_or_default();
+
+            let text = if schedules.is_empty() {
+                String::from("No active schedules.")
+            } else {
+                let mut lines: Vec<String> = schedules
+                    .iter()
+                    .map(|(id, entry)| {
+                        format!("{} - {} {}", id, entry.time.format("%H:%M"), entry.cadence)
+                    })
+                    .collect();
+                lines.sort();
+                lines.join("\n")
+            };
+
+            send_msg(&bot, &chat_id, &text, false).await
+        }
+
+        Command::Unschedule { id } => {
+            let (chat, removed) = state.remove_schedule(&chat_id, &id);
+            sc.write_chat(chat_id, chat).await.ok()?;
+
+            let text = if removed {
+                format!("Schedule {} cancelled.", id)
+            } else {
+                format!("No schedule with id {}.", id)
+            };
+            send_msg(&bot, &chat_id, &text, false).await
+        }
     }
 
     Some(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn t(hh: u32, mm: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hh, mm, 0).unwrap()
+    }
+
+    #[test]
+    fn daily_later_today_is_zero_days_ahead() {
+        assert_eq!(days_ahead(Cadence::Daily, Weekday::Mon, t(10, 0), t(20, 0)), 0);
+    }
+
+    #[test]
+    fn daily_already_passed_today_is_tomorrow() {
+        assert_eq!(days_ahead(Cadence::Daily, Weekday::Mon, t(20, 0), t(10, 0)), 1);
+    }
+
+    #[test]
+    fn daily_exact_now_counts_as_passed() {
+        assert_eq!(days_ahead(Cadence::Daily, Weekday::Mon, t(10, 0), t(10, 0)), 1);
+    }
+
+    #[test]
+    fn weekly_same_day_later_today_is_zero_days_ahead() {
+        assert_eq!(
+            days_ahead(Cadence::Weekly(Weekday::Mon), Weekday::Mon, t(10, 0), t(20, 0)),
+            0
+        );
+    }
+
+    #[test]
+    fn weekly_same_day_already_passed_wraps_to_next_week() {
+        assert_eq!(
+            days_ahead(Cadence::Weekly(Weekday::Mon), Weekday::Mon, t(20, 0), t(10, 0)),
+            7
+        );
+    }
+
+    #[test]
+    fn weekly_later_in_the_week() {
+        assert_eq!(
+            days_ahead(Cadence::Weekly(Weekday::Fri), Weekday::Mon, t(10, 0), t(20, 0)),
+            4
+        );
+    }
+
+    #[test]
+    fn weekly_earlier_in_the_week_wraps_around() {
+        assert_eq!(
+            days_ahead(Cadence::Weekly(Weekday::Mon), Weekday::Fri, t(10, 0), t(20, 0)),
+            3
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::Result;
+use chrono::NaiveTime;
+use teloxide::utils::command::{BotCommands, ParseError};
+
+/// Commands understood by the bot.
+#[derive(BotCommands, Clone, Debug)]
+#[command(rename_rule = "lowercase")]
+pub enum Command {
+    /// Show this help message.
+    Help,
+
+    /// Join or leave the instant queue, e.g. `/add` or `/add someone` to
+    /// add/remove another user on their behalf. Timed queues (e.g.
+    /// `/2000`) are handled separately, in `parse_cmd`.
+    #[command(rename = "add", parse_with = parse_add_remove)]
+    AddRemove {
+        time: Option<NaiveTime>,
+        for_user: Option<String>,
+    },
+
+    /// Leave every queue in this chat.
+    #[command(rename = "rmall")]
+    RemoveAll,
+
+    /// List all active queues in this chat.
+    List,
+
+    /// Records a finished match's result for Elo purposes, e.g.
+    /// `/result 20:00 1` if team 1 won the `/2000` queue, or `/result add 2`
+    /// if team 2 won the instant queue.
+    #[command(parse_with = "split", separator = " ")]
+    Result { queue: String, winning_team: u8 },
+
+    /// Removes `user` from every queue in this chat. Admin-only.
+    Kick { user: String },
+
+    /// Blocks `user` from joining queues in this chat for
+    /// `duration_minutes` minutes. Admin-only.
+    #[command(parse_with = "split", separator = " ")]
+    Block { user: String, duration_minutes: i64 },
+
+    /// Lifts a block on `user` in this chat. Admin-only.
+    Unblock { user: String },
+
+    /// Registers a recurring queue, e.g. `/schedule 2000 daily` or
+    /// `/schedule 1930 fri`. `cadence` is `daily` or a weekday
+    /// abbreviation (`mon`..`sun`).
+    #[command(parse_with = "split", separator = " ")]
+    Schedule { time: String, cadence: String },
+
+    /// Lists this chat's recurring schedules.
+    Schedules,
+
+    /// Cancels a recurring schedule by the id shown in `/schedules`.
+    Unschedule { id: String },
+}
+
+/// Custom parser for `AddRemove`'s arguments: `time` is never populated
+/// through this path (`/HHMM` is intercepted earlier in `parse_cmd`, before
+/// `Command::parse` ever runs), and neither `Option<NaiveTime>` nor
+/// `Option<String>` implements `FromStr`, which the derive's built-in
+/// "split" parser requires. So just take the rest of the line, if any, as
+/// the target username.
+fn parse_add_remove(input: String) -> Result<(Option<NaiveTime>, Option<String>), ParseError> {
+    let for_user = input.trim();
+    let for_user = (!for_user.is_empty()).then(|| for_user.to_string());
+
+    Ok((None, for_user))
+}
+
+/// Parses a raw message string into a `Command`, if it looks like one.
+/// Returns `Ok(None)` for ordinary, non-command messages.
+pub fn parse_cmd(text: &str) -> Result<Option<Command>> {
+    if !text.starts_with('/') {
+        return Ok(None);
+    }
+
+    // Timed queue commands look like `/HHMM`, which isn't a command
+    // `BotCommands` knows how to parse directly, so handle it here.
+    let stripped = text.trim_start_matches('/');
+    if stripped.len() == 4 && stripped.chars().all(|c| c.is_ascii_digit()) {
+        let time = NaiveTime::parse_from_str(stripped, "%H%M")?;
+        return Ok(Some(Command::AddRemove {
+            time: Some(time),
+            for_user: None,
+        }));
+    }
+
+    match Command::parse(text, "add_bot") {
+        Ok(cmd) => Ok(Some(cmd)),
+        Err(_) => Ok(None),
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    collections::HashMap,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use teloxide::{
+    payloads::SendMessageSetters, requests::Requester, types::ParseMode, Bot, RequestError,
+};
+use tokio::sync::{mpsc, Mutex};
+
+use crate::types::ChatId;
+
+/// Telegram allows roughly one message per second per chat, and ~30/sec
+/// globally across all chats. Stay comfortably under both.
+static PER_CHAT_MIN_INTERVAL: Duration = Duration::from_millis(1_050);
+static GLOBAL_MIN_INTERVAL: Duration = Duration::from_millis(35);
+static MAX_TRANSIENT_RETRIES: u32 = 5;
+static TRANSIENT_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+struct PendingSend {
+    chat_id: ChatId,
+    text: String,
+    markdown: bool,
+}
+
+/// Per-chat send queues plus the shared global rate gate. Lives for the
+/// process lifetime, mirroring how `StateContainer` is cloned around rather
+/// than rebuilt.
+struct Limiter {
+    chat_senders: Mutex<HashMap<ChatId, mpsc::UnboundedSender<PendingSend>>>,
+    global_last_send: Mutex<Instant>,
+}
+
+static LIMITER: OnceLock<Limiter> = OnceLock::new();
+
+fn limiter() -> &'static Limiter {
+    LIMITER.get_or_init(|| Limiter {
+        chat_senders: Mutex::new(HashMap::new()),
+        global_last_send: Mutex::new(Instant::now() - GLOBAL_MIN_INTERVAL),
+    })
+}
+
+/// Enqueues a message for `chat_id`. Never blocks the caller waiting on
+/// Telegram: the message is handed off to that chat's dedicated sender task
+/// (spawned on first use) and delivered in order, subject to per-chat and
+/// global rate limits.
+pub async fn enqueue(bot: Bot, chat_id: ChatId, text: String, markdown: bool) {
+    let limiter = limiter();
+    let mut senders = limiter.chat_senders.lock().await;
+
+    let tx = senders.entry(chat_id).or_insert_with(|| {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(drain_chat(bot, rx));
+        tx
+    });
+
+    // An unbounded channel only fails to send if the receiving task has
+    // panicked; there's no pending-message backlog to lose in that case.
+    let _ = tx.send(PendingSend {
+        chat_id,
+        text,
+        markdown,
+    });
+}
+
+/// Drains one chat's pending sends in order, honoring that chat's token
+/// bucket before each send.
+async fn drain_chat(bot: Bot, mut rx: mpsc::UnboundedReceiver<PendingSend>) {
+    let mut last_sent = Instant::now() - PER_CHAT_MIN_INTERVAL;
+
+    while let Some(pending) = rx.recv().await {
+        let since_last = last_sent.elapsed();
+        if since_last < PER_CHAT_MIN_INTERVAL {
+            tokio::time::sleep(PER_CHAT_MIN_INTERVAL - since_last).await;
+        }
+
+        send_with_retry(&bot, &pending).await;
+        last_sent = Instant::now();
+    }
+}
+
+/// Sends a single message, freezing and retrying the exact same request on
+/// `RetryAfter`, and retrying up to `MAX_TRANSIENT_RETRIES` times on any
+/// other error, after which the message is logged and dropped.
+async fn send_with_retry(bot: &Bot, pending: &PendingSend) {
+    let mut transient_retries = 0;
+
+    loop {
+        wait_for_global_slot().await;
+
+        let mut request = bot.send_message(pending.chat_id.0, &pending.text);
+        if pending.markdown {
+            request = request.parse_mode(ParseMode::Markdown);
+        }
+
+        match request.await {
+            Ok(_) => return,
+
+            Err(RequestError::RetryAfter(retry_after)) => {
+                let seconds = retry_after.as_secs();
+                log::warn!(
+                    "Rate limited on chat {:?}, freezing for {}s",
+                    pending.chat_id.0,
+                    seconds
+                );
+                tokio::time::sleep(Duration::from_secs(seconds)).await;
+                // Loop back around and retry the exact same request.
+            }
+
+            Err(err) if transient_retries < MAX_TRANSIENT_RETRIES => {
+                transient_retries += 1;
+                log::warn!(
+                    "Transient error sending to {:?} (attempt {}/{}): {}",
+                    pending.chat_id.0,
+                    transient_retries,
+                    MAX_TRANSIENT_RETRIES,
+                    err
+                );
+                tokio::time::sleep(TRANSIENT_RETRY_DELAY).await;
+            }
+
+            Err(err) => {
+                log::error!(
+                    "Giving up on message to {:?} after {} retries: {}",
+                    pending.chat_id.0,
+                    transient_retries,
+                    err
+                );
+                return;
+            }
+        }
+    }
+}
+
+/// Blocks until the global ~30/sec send budget has a free slot, then claims
+/// it.
+async fn wait_for_global_slot() {
+    loop {
+        let mut last = limiter().global_last_send.lock().await;
+        let since_last = last.elapsed();
+
+        if since_last >= GLOBAL_MIN_INTERVAL {
+            *last = Instant::now();
+            return;
+        }
+
+        let wait = GLOBAL_MIN_INTERVAL - since_last;
+        drop(last);
+        tokio::time::sleep(wait).await;
+    }
+}
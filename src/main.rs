@@ -1,29 +1,99 @@
-use crate::state_container::StateContainer;
+use std::sync::Arc;
+
+use crate::{
+    scheduler::Scheduler,
+    state::schedule_id,
+    state_container::StateContainer,
+    storage::{FileStorage, RedisStorage, SqliteStorage, Storage},
+    types::QueueId,
+};
 use anyhow::Result;
-use teloxide::{Bot, types::Message};
+use teloxide::{types::Message, Bot};
 
 mod bot;
 mod command;
+mod limiter;
+mod scheduler;
 mod state;
 mod state_container;
+mod storage;
 mod types;
 mod util;
 
+/// Picks the storage backend from `STORAGE_BACKEND`/`DATABASE_URL`, falling
+/// back to the file-backed store used by a single bot instance.
+async fn mk_storage() -> Result<Arc<dyn Storage>> {
+    let storage: Arc<dyn Storage> = match std::env::var("STORAGE_BACKEND").as_deref() {
+        Ok("sqlite") => {
+            let database_url = std::env::var("DATABASE_URL")?;
+            Arc::new(SqliteStorage::connect(&database_url).await?)
+        }
+        Ok("redis") => {
+            let redis_url = std::env::var("REDIS_URL")?;
+            Arc::new(RedisStorage::connect(&redis_url)?)
+        }
+        _ => Arc::new(FileStorage::default()),
+    };
+
+    Ok(storage)
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Try restoring state from file, or default to empty state.
-    let sc = StateContainer::try_read_from_file().await?;
+    // Restore state from the configured storage backend, or default to
+    // empty state.
+    let storage = mk_storage().await?;
+    let sc = StateContainer::new(storage).await?;
 
     // Initialize the Telegram bot API.
     pretty_env_logger::init();
     let bot = Bot::from_env();
 
-    // Spawn a new task that polls for queues that have timed out.
-    tokio::spawn(bot::poll_for_timeouts(sc.clone(), bot.clone()));
+    // Seed the queue scheduler with any queues restored from storage, then
+    // spawn a task that fires queue expirations as they come due.
+    let queue_scheduler = Scheduler::new();
+    for (chat_id, chat) in &sc.read().await?.chats {
+        for (queue_id, queue) in &chat.queues {
+            queue_scheduler
+                .schedule(*chat_id, queue_id.clone(), queue.timeout)
+                .await;
+        }
+    }
+    tokio::spawn(bot::run_scheduler(
+        sc.clone(),
+        bot.clone(),
+        queue_scheduler.clone(),
+    ));
+
+    // Seed the schedule scheduler with any recurring schedules restored
+    // from storage, then spawn a task that materializes their queues as
+    // they come due.
+    let schedule_scheduler = Scheduler::new();
+    for (chat_id, chat) in &sc.read().await?.chats {
+        for entry in chat.schedules.values() {
+            let at = bot::next_occurrence(entry.cadence, entry.time);
+            schedule_scheduler
+                .schedule_at(
+                    at,
+                    *chat_id,
+                    QueueId::new(schedule_id(entry.time, entry.cadence)),
+                    entry.time,
+                )
+                .await;
+        }
+    }
+    tokio::spawn(bot::run_recurring_schedules(
+        sc.clone(),
+        bot.clone(),
+        queue_scheduler.clone(),
+        schedule_scheduler.clone(),
+    ));
 
     // Start polling for Telegram messages.
     teloxide::repl(bot.clone(), move |message: Message, bot: Bot| {
         let sc = sc.clone();
+        let queue_scheduler = queue_scheduler.clone();
+        let schedule_scheduler = schedule_scheduler.clone();
         async move {
             let msg_text = message.text();
 
@@ -32,7 +102,8 @@ async fn main() -> Result<()> {
                 let cmd = command::parse_cmd(msg_text)?;
 
                 if let Some(cmd) = cmd {
-                    bot::handle_cmd(sc, bot, message, cmd).await;
+                    bot::handle_cmd(sc, bot, message, cmd, queue_scheduler, schedule_scheduler)
+                        .await;
                 }
             }
 
@@ -0,0 +1,132 @@
+use std::{cmp::Reverse, collections::BinaryHeap, sync::Arc};
+
+use chrono::{Local, NaiveTime};
+use tokio::{
+    sync::{Mutex, Notify},
+    time::Instant,
+};
+
+use crate::types::{ChatId, QueueId};
+
+/// A queue's scheduled expiration. `timeout` is kept alongside `at` so a
+/// popped entry can be checked against the queue's *current* timeout before
+/// acting on it — the queue may have been removed or rescheduled since this
+/// entry was pushed.
+#[derive(Debug, Clone)]
+pub struct Deadline {
+    pub at: Instant,
+    pub chat_id: ChatId,
+    pub queue_id: QueueId,
+    pub timeout: NaiveTime,
+}
+
+impl PartialEq for Deadline {
+    fn eq(&self, other: &Self) -> bool {
+        self.at == other.at
+    }
+}
+impl Eq for Deadline {}
+
+impl PartialOrd for Deadline {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Deadline {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.at.cmp(&other.at)
+    }
+}
+
+/// Converts a `HH:MM:SS` wall-clock time into the next `Instant` it occurs
+/// at, today or tomorrow.
+fn instant_for_time(time: NaiveTime) -> Instant {
+    let now_time = Local::now().time();
+
+    let mut until = time - now_time;
+    if until < chrono::Duration::zero() {
+        until = until + chrono::Duration::days(1);
+    }
+
+    Instant::now() + until.to_std().unwrap_or_default()
+}
+
+/// A min-heap of pending queue expirations, keyed by instant, with a
+/// `Notify` so pushing an earlier deadline can wake a scheduler that's
+/// sleeping until a later one.
+#[derive(Clone)]
+pub struct Scheduler {
+    heap: Arc<Mutex<BinaryHeap<Reverse<Deadline>>>>,
+    notify: Arc<Notify>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            heap: Arc::new(Mutex::new(BinaryHeap::new())),
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Schedules (or re-schedules) the expiration of `queue_id` in
+    /// `chat_id` at the next occurrence of `timeout`, today or tomorrow.
+    pub async fn schedule(&self, chat_id: ChatId, queue_id: QueueId, timeout: NaiveTime) {
+        self.schedule_at(instant_for_time(timeout), chat_id, queue_id, timeout)
+            .await;
+    }
+
+    /// Like `schedule`, but at a caller-computed `at` instead of the next
+    /// today-or-tomorrow occurrence of `timeout` — used by recurring
+    /// schedules, which may be due further out (e.g. a specific weekday).
+    pub async fn schedule_at(
+        &self,
+        at: Instant,
+        chat_id: ChatId,
+        queue_id: QueueId,
+        timeout: NaiveTime,
+    ) {
+        let deadline = Deadline {
+            at,
+            chat_id,
+            queue_id,
+            timeout,
+        };
+
+        self.heap.lock().await.push(Reverse(deadline));
+        self.notify.notify_one();
+    }
+
+    /// Returns the earliest pending deadline's instant, if any, without
+    /// removing it.
+    pub async fn peek_next(&self) -> Option<Instant> {
+        self.heap.lock().await.peek().map(|Reverse(d)| d.at)
+    }
+
+    /// Pops every deadline that is due by now.
+    pub async fn pop_due(&self) -> Vec<Deadline> {
+        let mut heap = self.heap.lock().await;
+        let now = Instant::now();
+        let mut due = Vec::new();
+
+        while let Some(Reverse(deadline)) = heap.peek() {
+            if deadline.at > now {
+                break;
+            }
+            due.push(heap.pop().unwrap().0);
+        }
+
+        due
+    }
+
+    /// Resolves once a new deadline has been scheduled.
+    pub async fn notified(&self) {
+        self.notify.notified().await
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,471 @@
+use std::{collections::HashMap, fmt};
+
+use chrono::{DateTime, NaiveTime, Utc, Weekday};
+
+use crate::types::{ChatId, QueueId};
+
+pub static QUEUE_SIZE: usize = 10;
+
+/// Rating a new player starts at, and the Elo K-factor used for updates.
+pub static DEFAULT_RATING: i32 = 1000;
+static ELO_K: f64 = 32.0;
+
+/// A single pickup queue: the players currently signed up, the command used
+/// to join it, and when it times out.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Queue {
+    pub players: Vec<String>,
+    pub add_cmd: String,
+    pub timeout: NaiveTime,
+    /// The balanced rosters proposed when the queue last filled, used by
+    /// `/result` to know who to apply the Elo update to.
+    pub teams: Option<(Vec<String>, Vec<String>)>,
+}
+
+impl Queue {
+    fn new(add_cmd: String, timeout: NaiveTime) -> Self {
+        Self {
+            players: Vec::new(),
+            add_cmd,
+            timeout,
+            teams: None,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.players.len() >= QUEUE_SIZE
+    }
+}
+
+/// How often a recurring queue re-creates itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Cadence {
+    Daily,
+    Weekly(Weekday),
+}
+
+impl Cadence {
+    /// Parses `daily` or a three-letter weekday abbreviation (`mon`, `tue`,
+    /// ...), case-insensitively.
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.to_lowercase().as_str() {
+            "daily" => Some(Cadence::Daily),
+            "mon" => Some(Cadence::Weekly(Weekday::Mon)),
+            "tue" => Some(Cadence::Weekly(Weekday::Tue)),
+            "wed" => Some(Cadence::Weekly(Weekday::Wed)),
+            "thu" => Some(Cadence::Weekly(Weekday::Thu)),
+            "fri" => Some(Cadence::Weekly(Weekday::Fri)),
+            "sat" => Some(Cadence::Weekly(Weekday::Sat)),
+            "sun" => Some(Cadence::Weekly(Weekday::Sun)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Cadence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cadence::Daily => write!(f, "daily"),
+            Cadence::Weekly(weekday) => write!(f, "{}", weekday.to_string().to_lowercase()),
+        }
+    }
+}
+
+/// A recurring queue template: re-creates a queue with this `time` on every
+/// occurrence of `cadence`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ScheduleEntry {
+    pub time: NaiveTime,
+    pub cadence: Cadence,
+}
+
+/// Deterministic id for a schedule, derived from its time and cadence so
+/// re-registering the same schedule updates it in place instead of
+/// duplicating it.
+pub fn schedule_id(time: NaiveTime, cadence: Cadence) -> String {
+    format!("{}-{}", time.format("%H%M"), cadence)
+}
+
+/// A single chat's queues, keyed by `QueueId`; any players an admin has
+/// temporarily blocked from joining; and any recurring schedules that
+/// re-create a queue on a cadence.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Chat {
+    pub queues: HashMap<QueueId, Queue>,
+    pub blocked: HashMap<String, DateTime<Utc>>,
+    pub schedules: HashMap<String, ScheduleEntry>,
+}
+
+/// Top-level bot state: all chats and their queues, plus every player's Elo
+/// rating (global, not scoped to a chat).
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct State {
+    pub chats: HashMap<ChatId, Chat>,
+    pub ratings: HashMap<String, i32>,
+}
+
+/// Describes what `add_remove_player` actually did, so callers can build an
+/// appropriate status message.
+#[derive(Debug, Clone)]
+pub enum AddRemovePlayerOp {
+    PlayerAdded(String),
+    PlayerRemoved(String),
+    Blocked(String),
+}
+
+/// Result of `add_remove_player`, carrying the resulting queue.
+#[derive(Debug, Clone)]
+pub enum AddRemovePlayerResult {
+    PlayerQueued(Queue),
+    QueueFull(Queue),
+    QueueEmpty(Queue),
+    /// The player is blocked in this chat until the given time.
+    PlayerBlocked(DateTime<Utc>),
+}
+
+impl State {
+    /// Toggles `username` in the given chat/queue: adds it if absent, removes
+    /// it if present. Creates the chat and/or queue if they don't exist yet.
+    /// Returns the affected chat along with the result and the op performed;
+    /// every operation here touches exactly one chat, so callers can persist
+    /// just that chat via `StateContainer::write_chat`.
+    pub fn add_remove_player(
+        &self,
+        chat_id: &ChatId,
+        queue_id: &QueueId,
+        add_cmd: String,
+        timeout: NaiveTime,
+        username: String,
+    ) -> (Chat, AddRemovePlayerResult, AddRemovePlayerOp) {
+        let mut chat = self.chats.get(chat_id).cloned().unwrap_or_default();
+
+        // Blocking only gates joining: a player already in the queue when
+        // an admin blocks them must still be able to leave via this same
+        // toggle, or they'd be stuck queued until the block expires.
+        let already_queued = chat
+            .queues
+            .get(queue_id)
+            .is_some_and(|queue| queue.players.iter().any(|p| p == &username));
+
+        if !already_queued {
+            if let Some(&blocked_until) = chat.blocked.get(&username) {
+                if blocked_until > Utc::now() {
+                    return (
+                        chat,
+                        AddRemovePlayerResult::PlayerBlocked(blocked_until),
+                        AddRemovePlayerOp::Blocked(username),
+                    );
+                }
+            }
+        }
+
+        let queue = chat
+            .queues
+            .entry(queue_id.clone())
+            .or_insert_with(|| Queue::new(add_cmd, timeout));
+
+        let op = if let Some(pos) = queue.players.iter().position(|p| p == &username) {
+            queue.players.remove(pos);
+            AddRemovePlayerOp::PlayerRemoved(username)
+        } else {
+            queue.players.push(username.clone());
+            AddRemovePlayerOp::PlayerAdded(username)
+        };
+
+        queue.teams = if queue.is_full() {
+            Some(self.split_balanced_teams(&queue.players))
+        } else {
+            None
+        };
+
+        let queue = queue.clone();
+        let result = if queue.players.is_empty() {
+            AddRemovePlayerResult::QueueEmpty(queue)
+        } else if queue.is_full() {
+            AddRemovePlayerResult::QueueFull(queue)
+        } else {
+            AddRemovePlayerResult::PlayerQueued(queue)
+        };
+
+        (chat, result, op)
+    }
+
+    /// Returns `username`'s current Elo rating, or `DEFAULT_RATING` if
+    /// they've never played.
+    pub fn rating_of(&self, username: &str) -> i32 {
+        *self.ratings.get(username).unwrap_or(&DEFAULT_RATING)
+    }
+
+    /// Splits `players` into two teams of (as close to) equal size whose
+    /// summed ratings are as close as possible, by brute-forcing every
+    /// even split. Fine for queue-sized player counts (single digits to
+    /// low tens).
+    fn split_balanced_teams(&self, players: &[String]) -> (Vec<String>, Vec<String>) {
+        let n = players.len();
+        let half = n / 2;
+        let total: i32 = players.iter().map(|p| self.rating_of(p)).sum();
+
+        let mut best_mask = 0u32;
+        let mut best_diff = i32::MAX;
+
+        for mask in 0u32..(1 << n) {
+            if mask.count_ones() as usize != half {
+                continue;
+            }
+
+            let team_a: i32 = (0..n)
+                .filter(|i| mask & (1 << i) != 0)
+                .map(|i| self.rating_of(&players[i]))
+                .sum();
+
+            let diff = (2 * team_a - total).abs();
+            if diff < best_diff {
+                best_diff = diff;
+                best_mask = mask;
+            }
+        }
+
+        let team_a = (0..n)
+            .filter(|i| best_mask & (1 << i) != 0)
+            .map(|i| players[i].clone())
+            .collect();
+        let team_b = (0..n)
+            .filter(|i| best_mask & (1 << i) == 0)
+            .map(|i| players[i].clone())
+            .collect();
+
+        (team_a, team_b)
+    }
+
+    /// Applies an Elo update for a finished match: `winners` each gain
+    /// `K * (1 - Ea)` and `losers` each lose `K * Ea`, where `Ea` is the
+    /// winning team's expected score given both teams' average ratings.
+    /// Returns the updated ratings map.
+    pub fn apply_match_result(
+        &self,
+        winners: &[String],
+        losers: &[String],
+    ) -> HashMap<String, i32> {
+        let mut ratings = self.ratings.clone();
+
+        let team_avg = |team: &[String]| -> f64 {
+            team.iter().map(|u| self.rating_of(u) as f64).sum::<f64>() / team.len() as f64
+        };
+
+        let ra = team_avg(winners);
+        let rb = team_avg(losers);
+        let ea = 1.0 / (1.0 + 10f64.powf((rb - ra) / 400.0));
+
+        let winner_delta = (ELO_K * (1.0 - ea)).round() as i32;
+        let loser_delta = (ELO_K * ea).round() as i32;
+
+        for username in winners {
+            let rating = ratings.entry(username.clone()).or_insert(DEFAULT_RATING);
+            *rating += winner_delta;
+        }
+        for username in losers {
+            let rating = ratings.entry(username.clone()).or_insert(DEFAULT_RATING);
+            *rating -= loser_delta;
+        }
+
+        ratings
+    }
+
+    /// Clears a queue's stored teams, e.g. once its result has been
+    /// recorded via `apply_match_result`, so `/result` can't be replayed
+    /// against it to reapply the same Elo update. Returns the affected
+    /// chat.
+    pub fn clear_queue_teams(&self, chat_id: &ChatId, queue_id: &QueueId) -> Chat {
+        let mut chat = self.chats.get(chat_id).cloned().unwrap_or_default();
+
+        if let Some(queue) = chat.queues.get_mut(queue_id) {
+            queue.teams = None;
+        }
+
+        chat
+    }
+
+    /// Removes `username` from every queue in the given chat. Returns the
+    /// affected chat along with all queues that were affected.
+    pub fn rm_player(&self, chat_id: &ChatId, username: &str) -> (Chat, Vec<(QueueId, Queue)>) {
+        let mut chat = self.chats.get(chat_id).cloned().unwrap_or_default();
+        let mut affected = Vec::new();
+
+        for (queue_id, queue) in chat.queues.iter_mut() {
+            if let Some(pos) = queue.players.iter().position(|p| p == username) {
+                queue.players.remove(pos);
+                affected.push((queue_id.clone(), queue.clone()));
+            }
+        }
+
+        (chat, affected)
+    }
+
+    /// Removes an entire queue from a chat, returning the affected chat
+    /// along with the removed queue, if it existed.
+    pub fn rm_chat_queue(&self, chat_id: &ChatId, queue_id: &QueueId) -> (Chat, Option<Queue>) {
+        let mut chat = self.chats.get(chat_id).cloned().unwrap_or_default();
+        let removed = chat.queues.remove(queue_id);
+
+        (chat, removed)
+    }
+
+    /// Blocks `username` from joining queues in this chat until `until`.
+    /// Returns the affected chat.
+    pub fn block_player(&self, chat_id: &ChatId, username: String, until: DateTime<Utc>) -> Chat {
+        let mut chat = self.chats.get(chat_id).cloned().unwrap_or_default();
+        chat.blocked.insert(username, until);
+
+        chat
+    }
+
+    /// Lifts a block on `username` in this chat. Returns the affected chat.
+    pub fn unblock_player(&self, chat_id: &ChatId, username: &str) -> Chat {
+        let mut chat = self.chats.get(chat_id).cloned().unwrap_or_default();
+        chat.blocked.remove(username);
+
+        chat
+    }
+
+    /// Registers (or updates, if the id already exists) a recurring
+    /// schedule. Returns the affected chat and the schedule's id.
+    pub fn add_schedule(
+        &self,
+        chat_id: &ChatId,
+        time: NaiveTime,
+        cadence: Cadence,
+    ) -> (Chat, String) {
+        let mut chat = self.chats.get(chat_id).cloned().unwrap_or_default();
+        let id = schedule_id(time, cadence);
+        chat.schedules
+            .insert(id.clone(), ScheduleEntry { time, cadence });
+
+        (chat, id)
+    }
+
+    /// Cancels a recurring schedule. Returns the affected chat and whether
+    /// a schedule with that id actually existed.
+    pub fn remove_schedule(&self, chat_id: &ChatId, id: &str) -> (Chat, bool) {
+        let mut chat = self.chats.get(chat_id).cloned().unwrap_or_default();
+        let removed = chat.schedules.remove(id).is_some();
+
+        (chat, removed)
+    }
+
+    /// Creates a fresh, empty queue at `time`, as a recurring schedule
+    /// firing does — unless a queue already exists at that id (e.g.
+    /// players already signed up for `/2000` manually before the 20:00
+    /// schedule fired), in which case it's left untouched rather than
+    /// overwritten. Returns the affected chat, the queue's id, the queue
+    /// itself, and whether it already existed.
+    pub fn materialize_scheduled_queue(
+        &self,
+        chat_id: &ChatId,
+        time: NaiveTime,
+    ) -> (Chat, QueueId, Queue, bool) {
+        let mut chat = self.chats.get(chat_id).cloned().unwrap_or_default();
+        let queue_id = QueueId::new(time.format("%H:%M").to_string());
+        let already_existed = chat.queues.contains_key(&queue_id);
+
+        let queue = chat
+            .queues
+            .entry(queue_id.clone())
+            .or_insert_with(|| {
+                let add_cmd = time.format("/%H%M").to_string();
+                Queue::new(add_cmd, time)
+            })
+            .clone();
+
+        (chat, queue_id, queue, already_existed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_with_ratings(ratings: &[(&str, i32)]) -> State {
+        State {
+            chats: HashMap::new(),
+            ratings: ratings
+                .iter()
+                .map(|(name, rating)| (name.to_string(), *rating))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn apply_match_result_favors_underdog_losses_less() {
+        // Winners start below the losers, so the expected score for an
+        // upset is low and the winners should gain more than `losers` lose
+        // as a fraction of K, rounding both sides independently.
+        let state = state_with_ratings(&[("alice", 900), ("bob", 1100)]);
+
+        let ratings = state.apply_match_result(
+            &["alice".to_string()],
+            &["bob".to_string()],
+        );
+
+        assert!(ratings["alice"] > 900);
+        assert!(ratings["bob"] < 1100);
+        // An upset win should be worth noticeably more than half of K.
+        assert!(ratings["alice"] - 900 > 16);
+        assert!(1100 - ratings["bob"] < 16);
+    }
+
+    #[test]
+    fn apply_match_result_even_teams_split_k_in_half() {
+        let state = state_with_ratings(&[("alice", 1000), ("bob", 1000)]);
+
+        let ratings = state.apply_match_result(
+            &["alice".to_string()],
+            &["bob".to_string()],
+        );
+
+        assert_eq!(ratings["alice"], 1000 + (ELO_K / 2.0).round() as i32);
+        assert_eq!(ratings["bob"], 1000 - (ELO_K / 2.0).round() as i32);
+    }
+
+    #[test]
+    fn apply_match_result_defaults_unrated_players() {
+        let state = state_with_ratings(&[]);
+
+        let ratings = state.apply_match_result(
+            &["alice".to_string()],
+            &["bob".to_string()],
+        );
+
+        assert_eq!(ratings["alice"], DEFAULT_RATING + (ELO_K / 2.0).round() as i32);
+        assert_eq!(ratings["bob"], DEFAULT_RATING - (ELO_K / 2.0).round() as i32);
+    }
+
+    #[test]
+    fn split_balanced_teams_minimizes_rating_gap() {
+        let state = state_with_ratings(&[
+            ("a", 2000),
+            ("b", 1000),
+            ("c", 1000),
+            ("d", 0),
+        ]);
+        let players = vec!["a".to_string(), "b".to_string(), "c".to_string(), "d".to_string()];
+
+        let (team_a, team_b) = state.split_balanced_teams(&players);
+
+        assert_eq!(team_a.len(), 2);
+        assert_eq!(team_b.len(), 2);
+        // The only perfectly even split is {a, d} vs {b, c} (2000 each).
+        let sum = |team: &[String]| -> i32 { team.iter().map(|p| state.rating_of(p)).sum() };
+        assert_eq!(sum(&team_a), sum(&team_b));
+    }
+
+    #[test]
+    fn split_balanced_teams_keeps_team_sizes_equal() {
+        let state = state_with_ratings(&[("a", 1000), ("b", 1000), ("c", 1000), ("d", 1000)]);
+        let players: Vec<String> = ["a", "b", "c", "d"].iter().map(|s| s.to_string()).collect();
+
+        let (team_a, team_b) = state.split_balanced_teams(&players);
+
+        assert_eq!(team_a.len(), team_b.len());
+        assert_eq!(team_a.len() + team_b.len(), players.len());
+    }
+}
@@ -0,0 +1,59 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+
+use crate::{
+    state::{Chat, State},
+    storage::Storage,
+    types::ChatId,
+};
+
+/// Shared, async-safe handle to the bot's `State`, backed by a pluggable
+/// `Storage` implementation. Cloning a `StateContainer` is cheap and yields
+/// another handle to the same underlying storage.
+///
+/// Deliberately uncached: every `read` goes straight to `storage`, so that
+/// multiple bot processes sharing a Sqlite/Redis backend see each other's
+/// writes on their very next command instead of only their own.
+#[derive(Clone)]
+pub struct StateContainer {
+    storage: Arc<dyn Storage>,
+}
+
+impl StateContainer {
+    pub async fn new(storage: Arc<dyn Storage>) -> Result<Self> {
+        Ok(Self { storage })
+    }
+
+    /// Loads the current state straight from `storage`. Reads every chat's
+    /// queues, so prefer `read_chat` when only one chat_id is needed (i.e.
+    /// almost always) — this is meant for startup, where every chat's
+    /// queues/schedules need re-arming anyway.
+    pub async fn read(&self) -> Result<State> {
+        self.storage.read_state().await
+    }
+
+    /// Loads just `chat_id`'s state plus every player's rating (needed for
+    /// Elo lookups and team balancing), without reading any other chat's
+    /// queues. Returns a `State` containing at most this one chat, so it
+    /// can be passed straight to `State`'s per-chat methods.
+    pub async fn read_chat(&self, chat_id: &ChatId) -> Result<State> {
+        let chat = self.storage.get_chat(chat_id).await?.unwrap_or_default();
+        let ratings = self.storage.read_ratings().await?;
+
+        let mut chats = HashMap::new();
+        chats.insert(*chat_id, chat);
+
+        Ok(State { chats, ratings })
+    }
+
+    /// Persists a single chat without touching any others.
+    pub async fn write_chat(&self, chat_id: ChatId, chat: Chat) -> Result<()> {
+        self.storage.put_chat(&chat_id, &chat).await
+    }
+
+    /// Persists a single player's rating without touching any others.
+    pub async fn write_rating(&self, username: String, rating: i32) -> Result<()> {
+        self.storage.put_rating(&username, rating).await
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::fs;
+
+use crate::state::{Chat, State};
+use crate::types::ChatId;
+
+use super::Storage;
+
+static DEFAULT_STATE_FILE_PATH: &str = "state.json";
+
+/// Persists the whole `State` to a single JSON file. Simple and fine for a
+/// single bot instance, but every write rewrites the entire file and
+/// concurrent instances would clobber each other; prefer `SqliteStorage` or
+/// `RedisStorage` when running more than one instance.
+pub struct FileStorage {
+    path: String,
+}
+
+impl FileStorage {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Default for FileStorage {
+    fn default() -> Self {
+        Self::new(DEFAULT_STATE_FILE_PATH)
+    }
+}
+
+#[async_trait]
+impl Storage for FileStorage {
+    async fn read_state(&self) -> Result<State> {
+        match fs::read_to_string(&self.path).await {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(_) => Ok(State::default()),
+        }
+    }
+
+    async fn write_state(&self, state: &State) -> Result<()> {
+        let serialized = serde_json::to_string(state)?;
+        fs::write(&self.path, serialized).await?;
+        Ok(())
+    }
+
+    async fn get_chat(&self, chat_id: &ChatId) -> Result<Option<Chat>> {
+        let state = self.read_state().await?;
+        Ok(state.chats.get(chat_id).cloned())
+    }
+
+    async fn put_chat(&self, chat_id: &ChatId, chat: &Chat) -> Result<()> {
+        // No per-row granularity in a flat file: read, patch the one chat,
+        // and rewrite the whole thing.
+        let mut state = self.read_state().await?;
+        state.chats.insert(*chat_id, chat.clone());
+        self.write_state(&state).await
+    }
+
+    async fn read_ratings(&self) -> Result<HashMap<String, i32>> {
+        // No per-field granularity in a flat file: the whole blob has to be
+        // read regardless, unlike Sqlite/Redis's dedicated ratings table.
+        Ok(self.read_state().await?.ratings)
+    }
+
+    async fn put_rating(&self, username: &str, rating: i32) -> Result<()> {
+        let mut state = self.read_state().await?;
+        state.ratings.insert(username.to_owned(), rating);
+        self.write_state(&state).await
+    }
+}
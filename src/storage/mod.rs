@@ -0,0 +1,43 @@
+mod file;
+mod redis;
+mod sqlite;
+
+pub use file::FileStorage;
+pub use redis::RedisStorage;
+pub use sqlite::SqliteStorage;
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::{
+    state::{Chat, State},
+    types::ChatId,
+};
+
+/// Persistence backend for `State`. Implementations decide how (and
+/// whether) to persist the whole blob versus individual chats; callers
+/// should prefer `get_chat`/`put_chat`/`read_ratings` when they only need
+/// one chat, so backends that can (Sqlite, Redis) avoid reading or writing
+/// unrelated data.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Loads the full state, e.g. on startup.
+    async fn read_state(&self) -> Result<State>;
+
+    /// Persists the full state.
+    async fn write_state(&self, state: &State) -> Result<()>;
+
+    /// Loads a single chat, if it has any stored state.
+    async fn get_chat(&self, chat_id: &ChatId) -> Result<Option<Chat>>;
+
+    /// Persists a single chat without touching any others.
+    async fn put_chat(&self, chat_id: &ChatId, chat: &Chat) -> Result<()>;
+
+    /// Loads every player's rating, without reading any chat's queues.
+    async fn read_ratings(&self) -> Result<HashMap<String, i32>>;
+
+    /// Persists a single player's rating without touching any others.
+    async fn put_rating(&self, username: &str, rating: i32) -> Result<()>;
+}
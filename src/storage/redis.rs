@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use redis::AsyncCommands;
+
+use crate::state::{Chat, State};
+use crate::types::ChatId;
+
+use super::Storage;
+
+static KEY_PREFIX: &str = "add-bot:chat:";
+static RATING_KEY_PREFIX: &str = "add-bot:rating:";
+
+/// Persists one key per chat in Redis, so multiple bot instances can share
+/// state without stepping on each other and a write only touches the
+/// changed chat's key.
+pub struct RedisStorage {
+    client: redis::Client,
+}
+
+impl RedisStorage {
+    pub fn connect(redis_url: &str) -> Result<Self> {
+        let client = redis::Client::open(redis_url)?;
+        Ok(Self { client })
+    }
+
+    fn chat_key(chat_id: &ChatId) -> String {
+        format!("{}{}", KEY_PREFIX, chat_id.0 .0)
+    }
+
+    fn rating_key(username: &str) -> String {
+        format!("{}{}", RATING_KEY_PREFIX, username)
+    }
+}
+
+#[async_trait]
+impl Storage for RedisStorage {
+    async fn read_state(&self) -> Result<State> {
+        let mut conn = self.client.get_async_connection().await?;
+        let keys: Vec<String> = conn.keys(format!("{}*", KEY_PREFIX)).await?;
+
+        let mut state = State::default();
+
+        for key in keys {
+            let data: String = conn.get(&key).await?;
+            let chat: Chat = serde_json::from_str(&data)?;
+            let raw_chat_id: i64 = key.trim_start_matches(KEY_PREFIX).parse()?;
+
+            state
+                .chats
+                .insert(ChatId::new(teloxide::types::ChatId(raw_chat_id)), chat);
+        }
+
+        state.ratings = self.read_ratings().await?;
+
+        Ok(state)
+    }
+
+    async fn write_state(&self, state: &State) -> Result<()> {
+        for (chat_id, chat) in &state.chats {
+            self.put_chat(chat_id, chat).await?;
+        }
+        for (username, rating) in &state.ratings {
+            self.put_rating(username, *rating).await?;
+        }
+        Ok(())
+    }
+
+    async fn get_chat(&self, chat_id: &ChatId) -> Result<Option<Chat>> {
+        let mut conn = self.client.get_async_connection().await?;
+        let data: Option<String> = conn.get(Self::chat_key(chat_id)).await?;
+
+        Ok(match data {
+            Some(data) => Some(serde_json::from_str(&data)?),
+            None => None,
+        })
+    }
+
+    async fn put_chat(&self, chat_id: &ChatId, chat: &Chat) -> Result<()> {
+        let mut conn = self.client.get_async_connection().await?;
+        let data = serde_json::to_string(chat)?;
+        conn.set::<_, _, ()>(Self::chat_key(chat_id), data).await?;
+        Ok(())
+    }
+
+    async fn read_ratings(&self) -> Result<HashMap<String, i32>> {
+        let mut conn = self.client.get_async_connection().await?;
+        let keys: Vec<String> = conn.keys(format!("{}*", RATING_KEY_PREFIX)).await?;
+
+        let mut ratings = HashMap::new();
+        for key in keys {
+            let rating: i32 = conn.get(&key).await?;
+            let username = key.trim_start_matches(RATING_KEY_PREFIX).to_owned();
+            ratings.insert(username, rating);
+        }
+
+        Ok(ratings)
+    }
+
+    async fn put_rating(&self, username: &str, rating: i32) -> Result<()> {
+        let mut conn = self.client.get_async_connection().await?;
+        conn.set::<_, _, ()>(Self::rating_key(username), rating)
+            .await?;
+        Ok(())
+    }
+}
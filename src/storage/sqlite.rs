@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+
+use crate::state::{Chat, Queue, ScheduleEntry, State};
+use crate::types::{ChatId, QueueId};
+
+use super::Storage;
+
+/// Persists state in Sqlite, one row per chat/queue, so a write only
+/// touches the one chat's rows instead of rewriting the whole state blob.
+/// `put_chat` deletes and re-inserts every queue row of that chat, whether
+/// or not the queue changed.
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let pool = SqlitePool::connect(database_url).await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS queues (
+                chat_id INTEGER NOT NULL,
+                queue_id TEXT NOT NULL,
+                data TEXT NOT NULL,
+                PRIMARY KEY (chat_id, queue_id)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS ratings (
+                username TEXT PRIMARY KEY,
+                rating INTEGER NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS chat_meta (
+                chat_id INTEGER PRIMARY KEY,
+                blocked TEXT NOT NULL,
+                schedules TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        Ok(Self { pool })
+    }
+}
+
+/// Loads `chat_id`'s `blocked`/`schedules` maps from `chat_meta`, if any
+/// row exists for it.
+async fn load_chat_meta(
+    pool: &SqlitePool,
+    chat_id: &ChatId,
+) -> Result<(HashMap<String, DateTime<Utc>>, HashMap<String, ScheduleEntry>)> {
+    let row = sqlx::query("SELECT blocked, schedules FROM chat_meta WHERE chat_id = ?")
+        .bind(chat_id.0 .0)
+        .fetch_optional(pool)
+        .await?;
+
+    match row {
+        Some(row) => {
+            let blocked: String = row.try_get("blocked")?;
+            let schedules: String = row.try_get("schedules")?;
+            Ok((serde_json::from_str(&blocked)?, serde_json::from_str(&schedules)?))
+        }
+        None => Ok((HashMap::new(), HashMap::new())),
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn read_state(&self) -> Result<State> {
+        let rows = sqlx::query("SELECT chat_id, queue_id, data FROM queues")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut state = State::default();
+
+        for row in rows {
+            let chat_id: i64 = row.try_get("chat_id")?;
+            let queue_id: String = row.try_get("queue_id")?;
+            let data: String = row.try_get("data")?;
+            let queue: Queue = serde_json::from_str(&data)?;
+
+            state
+                .chats
+                .entry(ChatId::new(teloxide::types::ChatId(chat_id)))
+                .or_default()
+                .queues
+                .insert(QueueId::new(queue_id), queue);
+        }
+
+        state.ratings = self.read_ratings().await?;
+
+        let meta_rows = sqlx::query("SELECT chat_id, blocked, schedules FROM chat_meta")
+            .fetch_all(&self.pool)
+            .await?;
+
+        for row in meta_rows {
+            let chat_id: i64 = row.try_get("chat_id")?;
+            let blocked: String = row.try_get("blocked")?;
+            let schedules: String = row.try_get("schedules")?;
+
+            let chat = state
+                .chats
+                .entry(ChatId::new(teloxide::types::ChatId(chat_id)))
+                .or_default();
+            chat.blocked = serde_json::from_str(&blocked)?;
+            chat.schedules = serde_json::from_str(&schedules)?;
+        }
+
+        Ok(state)
+    }
+
+    async fn write_state(&self, state: &State) -> Result<()> {
+        for (chat_id, chat) in &state.chats {
+            self.put_chat(chat_id, chat).await?;
+        }
+        for (username, rating) in &state.ratings {
+            self.put_rating(username, *rating).await?;
+        }
+        Ok(())
+    }
+
+    async fn get_chat(&self, chat_id: &ChatId) -> Result<Option<Chat>> {
+        let rows = sqlx::query("SELECT queue_id, data FROM queues WHERE chat_id = ?")
+            .bind(chat_id.0 .0)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let (blocked, schedules) = load_chat_meta(&self.pool, chat_id).await?;
+
+        if rows.is_empty() && blocked.is_empty() && schedules.is_empty() {
+            return Ok(None);
+        }
+
+        let mut chat = Chat {
+            blocked,
+            schedules,
+            ..Chat::default()
+        };
+
+        for row in rows {
+            let queue_id: String = row.try_get("queue_id")?;
+            let data: String = row.try_get("data")?;
+            chat.queues
+                .insert(QueueId::new(queue_id), serde_json::from_str(&data)?);
+        }
+
+        Ok(Some(chat))
+    }
+
+    async fn put_chat(&self, chat_id: &ChatId, chat: &Chat) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM queues WHERE chat_id = ?")
+            .bind(chat_id.0 .0)
+            .execute(&mut *tx)
+            .await?;
+
+        for (queue_id, queue) in &chat.queues {
+            let data = serde_json::to_string(queue)?;
+
+            sqlx::query("INSERT INTO queues (chat_id, queue_id, data) VALUES (?, ?, ?)")
+                .bind(chat_id.0 .0)
+                .bind(&queue_id.0)
+                .bind(data)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let blocked = serde_json::to_string(&chat.blocked)?;
+        let schedules = serde_json::to_string(&chat.schedules)?;
+
+        sqlx::query(
+            "INSERT INTO chat_meta (chat_id, blocked, schedules) VALUES (?, ?, ?)
+             ON CONFLICT (chat_id) DO UPDATE SET blocked = excluded.blocked, schedules = excluded.schedules",
+        )
+        .bind(chat_id.0 .0)
+        .bind(blocked)
+        .bind(schedules)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn read_ratings(&self) -> Result<HashMap<String, i32>> {
+        let rows = sqlx::query("SELECT username, rating FROM ratings")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut ratings = HashMap::new();
+        for row in rows {
+            let username: String = row.try_get("username")?;
+            let rating: i32 = row.try_get("rating")?;
+            ratings.insert(username, rating);
+        }
+
+        Ok(ratings)
+    }
+
+    async fn put_rating(&self, username: &str, rating: i32) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO ratings (username, rating) VALUES (?, ?)
+             ON CONFLICT (username) DO UPDATE SET rating = excluded.rating",
+        )
+        .bind(username)
+        .bind(rating)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
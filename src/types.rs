@@ -0,0 +1,40 @@
+use std::fmt;
+
+/// Wrapper around a Telegram chat id, used as the top-level key into `State`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct ChatId(pub teloxide::types::ChatId);
+
+impl ChatId {
+    pub fn new(id: teloxide::types::ChatId) -> Self {
+        Self(id)
+    }
+}
+
+/// Identifier for a queue within a chat. Timed queues are keyed by their
+/// `HH:MM` timeout string, while the single "instant" queue uses the empty
+/// string.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub struct QueueId(pub String);
+
+impl QueueId {
+    pub fn new(id: String) -> Self {
+        Self(id)
+    }
+
+    /// Instant queues (created via `/add`) are keyed by the empty string.
+    pub fn is_instant_queue(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for QueueId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_instant_queue() {
+            write!(f, "/add")
+        } else {
+            write!(f, "/{}", self.0)
+        }
+    }
+}
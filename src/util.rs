@@ -0,0 +1,80 @@
+use chrono::NaiveTime;
+use teloxide::{types::User, Bot};
+
+use crate::{
+    state::{AddRemovePlayerOp, Queue, QUEUE_SIZE},
+    types::{ChatId, QueueId},
+};
+
+/// Formats a `NaiveTime` as `HH:MM`, which is how queue timeouts are keyed
+/// and displayed.
+pub fn fmt_naive_time(time: &NaiveTime) -> String {
+    time.format("%H:%M").to_string()
+}
+
+/// Builds the Telegram handle for `user`, preferring `@username` and falling
+/// back to their first name.
+pub fn mk_username(user: &User) -> String {
+    match &user.username {
+        Some(username) => format!("@{}", username),
+        None => user.first_name.clone(),
+    }
+}
+
+/// Renders a queue's players as a human-readable, newline-joined list.
+/// `numbered` prefixes each player with their position; `show_empty_slots`
+/// pads the list out to the queue size with empty slots.
+pub fn mk_players_str(queue: &Queue, numbered: bool, show_empty_slots: bool) -> String {
+    let mut lines: Vec<String> = queue
+        .players
+        .iter()
+        .enumerate()
+        .map(|(i, player)| {
+            if numbered {
+                format!("{}. {}", i + 1, player)
+            } else {
+                player.clone()
+            }
+        })
+        .collect();
+
+    if show_empty_slots {
+        while lines.len() < QUEUE_SIZE {
+            lines.push(String::from("-"));
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// Builds the status message sent after a player is added to or removed from
+/// a queue.
+pub fn mk_queue_status_msg(queue: &Queue, queue_id: &QueueId, op: &AddRemovePlayerOp) -> String {
+    let action = match op {
+        AddRemovePlayerOp::PlayerAdded(username) => format!("{} joined", username),
+        AddRemovePlayerOp::PlayerRemoved(username) => format!("{} left", username),
+        // Blocked players never reach here: callers render
+        // `AddRemovePlayerResult::PlayerBlocked` as its own message instead
+        // of calling this function.
+        AddRemovePlayerOp::Blocked(_) => unreachable!("blocked players don't get a queue status message"),
+    };
+
+    let players_str = mk_players_str(queue, false, false);
+
+    format!(
+        "{} {} queue ({}/{}):\n{}",
+        action,
+        queue_id,
+        queue.players.len(),
+        10,
+        players_str
+    )
+}
+
+/// Sends `text` to `chat_id`, optionally rendering it as Markdown. The
+/// message is handed off to `limiter` rather than sent directly, so bursts
+/// of sends (e.g. a timeout storm or a mass `/rmall`) can't get the bot
+/// rate-limited or lose messages.
+pub async fn send_msg(bot: &Bot, chat_id: &ChatId, text: &str, markdown: bool) {
+    crate::limiter::enqueue(bot.clone(), *chat_id, text.to_owned(), markdown).await;
+}